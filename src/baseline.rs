@@ -0,0 +1,177 @@
+//! A module for recording and diffing binary size baselines.
+//!
+//! A baseline is a small JSON file recording the `(code, data)` size of a
+//! previous run. Comparing against it turns `cargo size` into a regression
+//! tracker: firmware projects can watch their flash/RAM budget shrink or grow
+//! across commits.
+use crate::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// A size snapshot, as recorded in (and read back from) a `--baseline` file.
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    /// The recorded program (code) size, in bytes.
+    pub program_bytes: u64,
+    /// The recorded data size, in bytes.
+    pub data_bytes: u64,
+    /// The recorded per-region (`FLASH`/`RAM`/`CCMRAM`/...) usage, in bytes,
+    /// as `(name, used_bytes)` pairs.
+    ///
+    /// Empty if the binary carried no load addresses or `memory.x` declared
+    /// no regions at the time the baseline was recorded.
+    pub regions: Vec<(String, u64)>,
+}
+
+/// Read a previously recorded baseline from `path`.
+///
+/// Returns `None` if the file does not exist yet or could not be parsed; in
+/// both cases the caller should simply record a fresh baseline instead of
+/// printing a diff.
+pub fn read(path: &Path) -> Option<Baseline> {
+    let content = fs::read_to_string(path).ok()?;
+
+    Some(Baseline {
+        program_bytes: json_u64_field(&content, "program_bytes")?,
+        data_bytes: json_u64_field(&content, "data_bytes")?,
+        regions: json_region_array(&content),
+    })
+}
+
+/// Record the current `(code, data)` size, together with the per-region
+/// usage (`(name, used_bytes, _)` triples, as returned by
+/// [`region_usage`][region_usage]), to `path`, overwriting any previous
+/// baseline.
+///
+/// [region_usage]: ../fn.region_usage.html
+pub fn write(
+    path: &Path,
+    code: u64,
+    data: u64,
+    regions: &[(String, u64, u64)],
+) -> Result<(), Error> {
+    let mut json = format!(
+        "{{\n  \"program_bytes\": {},\n  \"data_bytes\": {}",
+        code, data
+    );
+
+    if !regions.is_empty() {
+        let entries: Vec<String> = regions
+            .iter()
+            .map(|(name, used, _)| format!("{{ \"name\": {:?}, \"used_bytes\": {} }}", name, used))
+            .collect();
+        json += &format!(",\n  \"regions\": [{}]", entries.join(", "));
+    }
+
+    json += "\n}\n";
+    fs::write(path, json)?;
+
+    Ok(())
+}
+
+/// Extract the `u64` value of the top-level JSON field `key` out of
+/// `content`.
+///
+/// This only handles the compact JSON objects written by [`write`][write]; it
+/// is not a general purpose JSON parser.
+///
+/// [write]: fn.write.html
+fn json_u64_field(content: &str, key: &str) -> Option<u64> {
+    let needle = format!("\"{}\":", key);
+    let value = content[content.find(&needle)? + needle.len()..].trim_start();
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+
+    value[..end].parse().ok()
+}
+
+/// Extract the string value of the top-level JSON field `key` out of
+/// `content`.
+///
+/// This only handles the compact JSON objects written by [`write`][write]; it
+/// is not a general purpose JSON parser. Returns `None` if `key` is absent.
+///
+/// [write]: fn.write.html
+fn json_string_field(content: &str, key: &str) -> Option<String> {
+    let needle = format!("\"{}\":", key);
+    let value = content[content.find(&needle)? + needle.len()..].trim_start();
+    let value = value.strip_prefix('"')?;
+    let end = value.find('"')?;
+
+    Some(value[..end].to_owned())
+}
+
+/// Parse the `"regions"` array, as written by [`write`][write], out of a
+/// baseline file's `content`.
+///
+/// Returns an empty `Vec` if the field is absent.
+///
+/// [write]: fn.write.html
+fn json_region_array(content: &str) -> Vec<(String, u64)> {
+    let needle = "\"regions\":";
+    let start = match content.find(needle) {
+        Some(start) => start + needle.len(),
+        None => return Vec::new(),
+    };
+    let rest = content[start..].trim_start();
+    let end = match rest.find(']') {
+        Some(end) => end,
+        None => return Vec::new(),
+    };
+
+    rest[..end]
+        .split('{')
+        .skip(1)
+        .filter_map(|entry| {
+            let name = json_string_field(entry, "name")?;
+            let used = json_u64_field(entry, "used_bytes")?;
+            Some((name, used))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, write};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn baseline_round_trips_through_a_file() {
+        let path = env::temp_dir().join("cargo_size_baseline_round_trip_test.json");
+        let regions = vec![
+            ("FLASH".to_owned(), 1234, 65536),
+            ("RAM".to_owned(), 56, 8192),
+        ];
+
+        write(&path, 1234, 56, &regions).unwrap();
+        let baseline = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(baseline.program_bytes, 1234);
+        assert_eq!(baseline.data_bytes, 56);
+        assert_eq!(
+            baseline.regions,
+            vec![("FLASH".to_owned(), 1234), ("RAM".to_owned(), 56)]
+        );
+    }
+
+    #[test]
+    fn baseline_without_regions_round_trips_with_an_empty_list() {
+        let path = env::temp_dir().join("cargo_size_baseline_no_regions_test.json");
+
+        write(&path, 42, 8, &[]).unwrap();
+        let baseline = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(baseline.regions.is_empty());
+    }
+
+    #[test]
+    fn read_returns_none_for_a_missing_file() {
+        let path = env::temp_dir().join("cargo_size_baseline_does_not_exist_test.json");
+
+        assert!(read(&path).is_none());
+    }
+}
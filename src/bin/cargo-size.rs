@@ -4,8 +4,11 @@ extern crate cargo_size;
 use cargo_size::binary;
 use cargo_size::change_directory;
 use cargo_size::error::Error;
-use cargo_size::memory_size;
-use cargo_size::mode::Mode;
+use cargo_size::mode::{Mode, OutputFormat};
+use cargo_size::{
+    format_baseline_diff, format_breakdown, format_json, format_size, memory_regions, memory_size,
+    region_usage, update_baseline,
+};
 use colored::Colorize;
 use std::env;
 use std::process;
@@ -15,31 +18,96 @@ use std::process;
 /// On success, the function returns the program output.
 fn try_main() -> Result<String, Error> {
     let mode = Mode::new();
+    let human = env::args().any(|arg| arg == "--human");
 
     change_directory()?;
-    mode.build_binary()?;
-    let binary = mode.binary()?;
+    let binary = mode.build_binary()?;
     let (code, data) = binary::read_size_from(&binary)?;
 
-    if let Some((code_memory, data_memory)) = memory_size() {
+    let crates = if env::args().any(|arg| arg == "--crates") {
+        let symbols = binary::read_symbols_from(&binary)?;
+        Some(binary::crate_sizes(&symbols))
+    } else {
+        None
+    };
+
+    let sections = binary::read_sections_from(&binary)?;
+    let regions = memory_regions();
+    let usage = region_usage(&regions, &sections);
+
+    let previous_baseline = update_baseline(code, data, usage.as_deref())?;
+
+    if let OutputFormat::Json = OutputFormat::new() {
+        return Ok(format_json(
+            code,
+            data,
+            memory_size(),
+            usage.as_deref(),
+            crates.as_deref(),
+            previous_baseline.as_ref(),
+        ));
+    }
+
+    let mut output = if let Some(usage) = &usage {
+        let mut text = format!(
+            "Memory Usage
+             ------------
+             Program: {}
+             Data:    {}",
+            format_size(code, human),
+            format_size(data, human)
+        );
+        for (name, used, length) in usage {
+            let percentage = *used as f32 / *length as f32 * 100.0;
+            text += &format!(
+                "\n             {:<9}{} ({:.1}% full)",
+                format!("{}:", name.to_uppercase()),
+                format_size(*used, human),
+                percentage
+            );
+        }
+        text
+    } else if let Some((code_memory, data_memory)) = memory_size() {
         let code_percentage = code as f32 / code_memory as f32 * 100.0;
         let data_percentage = data as f32 / data_memory as f32 * 100.0;
-        Ok(format!(
+        format!(
             "Memory Usage
              ------------
-             Program: {:>7} bytes ({:.1}% full)
-             Data:    {:>7} bytes ({:.1}% full)",
-            code, code_percentage, data, data_percentage
-        ))
+             Program: {} ({:.1}% full)
+             Data:    {} ({:.1}% full)",
+            format_size(code, human),
+            code_percentage,
+            format_size(data, human),
+            data_percentage
+        )
     } else {
-        Ok(format!(
+        format!(
             "Memory Usage
              ------------
-             Program: {:>7} bytes
-             Data:    {:>7} bytes",
-            code, data
-        ))
+             Program: {}
+             Data:    {}",
+            format_size(code, human),
+            format_size(data, human)
+        )
+    };
+
+    if let Some(crates) = &crates {
+        output += &format_breakdown(crates, crates.iter().map(|(_, size)| size).sum(), human);
+    } else if env::args().any(|arg| arg == "--symbols") {
+        let symbols = binary::sorted_symbols(binary::read_symbols_from(&binary)?);
+        let total = symbols.iter().map(|symbol| symbol.size).sum();
+        let entries: Vec<_> = symbols
+            .into_iter()
+            .map(|symbol| (symbol.name, symbol.size))
+            .collect();
+        output += &format_breakdown(&entries, total, human);
     }
+
+    if let Some(previous) = &previous_baseline {
+        output += &format_baseline_diff(previous, code, data, usage.as_deref(), human);
+    }
+
+    Ok(output)
 }
 
 /// The program entry point.
@@ -64,6 +132,14 @@ USAGE:
 OPTIONS:
       --release               Print the size of the release binary
                               (debug if flag is not present)
+      --crates                Print a per-crate size breakdown
+      --symbols               Print a per-symbol size breakdown
+      --message-format=json   Print the result as machine readable JSON
+                              (same as --output json)
+      --human                 Print sizes with binary unit suffixes
+                              (e.g. 54.3 KiB) instead of raw byte counts
+      --baseline <file>       Compare against (and update) a recorded
+                              size baseline
       --help                  Print this help screen and exit
       --version               Print the version number and exit",
             name
@@ -1,28 +1,101 @@
 //! A module containing functions for interacting with binaries.
 use crate::error::Error;
+use std::collections::HashMap;
+use std::fs;
 use std::path::Path;
 
-/// All sections, that contain program code.
+/// All ELF sections, that contain program code.
 ///
 /// The size of those sections (if present) are added up in order to calculate
 /// the program size.
 pub const PROGRAM_SECTIONS: [&str; 3] = [".vector_table", ".text", ".rodata"];
 
-/// All sections, that contain program data.
+/// All ELF sections, that contain program data.
 ///
 /// The size of those sections (if present) are added up in order to calculate
 /// the data size.
 pub const DATA_SECTIONS: [&str; 2] = [".bss", ".data"];
 
+/// The Mach-O `(segment, section)` pairs that contain program code.
+///
+/// `binfarce` only exposes sections by exact `(segment, section)` name (no
+/// iteration over all sections of a segment), so these are concrete section
+/// names rather than whole segments.
+pub const MACHO_PROGRAM_SECTIONS: [(&str, &str); 1] = [("__TEXT", "__text")];
+
+/// The Mach-O `(segment, section)` pairs that contain program data.
+pub const MACHO_DATA_SECTIONS: [(&str, &str); 1] = [("__DATA", "__data")];
+
+/// The PE/COFF sections that contain program code.
+pub const PE_PROGRAM_SECTIONS: [&str; 2] = [".text", ".rdata"];
+
+/// The PE/COFF sections that contain program data.
+pub const PE_DATA_SECTIONS: [&str; 1] = [".data"];
+
+/// The name of the bucket symbols are put into, if they cannot be attributed
+/// to a crate.
+///
+/// This happens for symbols that are not Rust-mangled (e.g. symbols coming
+/// from a C library) or whose demangled name has no `::`-separated crate
+/// segment.
+pub const UNKNOWN_CRATE: &str = "[Unknown]";
+
+/// The binary format, as determined by [`detect_format`][detect].
+///
+/// [detect]: fn.detect_format.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Elf,
+    MachO,
+    Pe,
+}
+
+/// Sniff the magic number at the start of `bytes` and determine the binary
+/// format it was taken from.
+///
+/// Returns `None` if the format is not recognized.
+fn detect_format(bytes: &[u8]) -> Option<Format> {
+    match bytes {
+        [0x7f, b'E', b'L', b'F', ..] => Some(Format::Elf),
+        [0xfe, 0xed, 0xfa, 0xce, ..]
+        | [0xfe, 0xed, 0xfa, 0xcf, ..]
+        | [0xce, 0xfa, 0xed, 0xfe, ..]
+        | [0xcf, 0xfa, 0xed, 0xfe, ..] => Some(Format::MachO),
+        [b'M', b'Z', ..] => Some(Format::Pe),
+        _ => None,
+    }
+}
+
 /// Read the code and data size from the binary.
 ///
-/// The size of the sections listed in [`PROGRAM_SECTIONS`][program] and
-/// [`DATA_SECTIONS`][data] are added up and returned. Non-existing sections
-/// are ignored.
+/// The file's magic number is sniffed to determine whether it is an ELF,
+/// Mach-O or PE/COFF binary; each format's code and data sections (see
+/// [`PROGRAM_SECTIONS`][program]/[`DATA_SECTIONS`][data] for ELF and their
+/// per-format counterparts for the other formats) are added up and mapped
+/// onto the same `(code, data)` tuple. Non-existing sections are ignored.
+///
+/// # Errors
+/// Returns [`Error::UnsupportedFormat`][unsupported] if the format cannot be
+/// determined, or [`Error::InvalidBinary`][invalid] if the file claims to be
+/// of a known format but is corrupt.
 ///
 /// [program]: constant.PROGRAM_SECTIONS.html
 /// [data]: constant.DATA_SECTIONS.html
+/// [unsupported]: ../error/enum.Error.html#variant.UnsupportedFormat
+/// [invalid]: ../error/enum.Error.html#variant.InvalidBinary
 pub fn read_size_from(file: &Path) -> Result<(u64, u64), Error> {
+    let bytes = fs::read(file)?;
+
+    match detect_format(&bytes) {
+        Some(Format::Elf) => read_elf_size(file),
+        Some(Format::MachO) => read_macho_size(&bytes),
+        Some(Format::Pe) => read_pe_size(&bytes),
+        None => Err(Error::UnsupportedFormat),
+    }
+}
+
+/// Read the code and data size of an ELF binary.
+fn read_elf_size(file: &Path) -> Result<(u64, u64), Error> {
     let file = elf::File::open_path(file)?;
 
     let code = PROGRAM_SECTIONS
@@ -41,3 +114,464 @@ pub fn read_size_from(file: &Path) -> Result<(u64, u64), Error> {
 
     Ok((code, data))
 }
+
+/// Read the code and data size of a Mach-O binary.
+fn read_macho_size(bytes: &[u8]) -> Result<(u64, u64), Error> {
+    let macho = binfarce::macho::parse(bytes).map_err(|_| Error::InvalidBinary)?;
+
+    let mut code = 0;
+    let mut data = 0;
+    for (segment, section) in MACHO_PROGRAM_SECTIONS {
+        code += macho_section_size(&macho, segment, section)?;
+    }
+    for (segment, section) in MACHO_DATA_SECTIONS {
+        data += macho_section_size(&macho, segment, section)?;
+    }
+
+    Ok((code, data))
+}
+
+/// Look up a single Mach-O section by its `segment`/`name` pair and return its
+/// on-disk size in bytes, or `0` if the binary does not contain it.
+fn macho_section_size(
+    macho: &binfarce::macho::Macho<'_>,
+    segment: &str,
+    name: &str,
+) -> Result<u64, Error> {
+    match macho
+        .section_with_name(segment, name)
+        .map_err(|_| Error::InvalidBinary)?
+    {
+        Some(section) => Ok(section.range().map_err(|_| Error::InvalidBinary)?.len() as u64),
+        None => Ok(0),
+    }
+}
+
+/// Read the code and data size of a PE/COFF binary.
+fn read_pe_size(bytes: &[u8]) -> Result<(u64, u64), Error> {
+    let pe = binfarce::pe::parse(bytes).map_err(|_| Error::InvalidBinary)?;
+
+    let mut code = 0;
+    let mut data = 0;
+    for name in PE_PROGRAM_SECTIONS {
+        code += pe_section_size(&pe, name)?;
+    }
+    for name in PE_DATA_SECTIONS {
+        data += pe_section_size(&pe, name)?;
+    }
+
+    Ok((code, data))
+}
+
+/// Look up a single PE/COFF section by `name` and return its on-disk size in
+/// bytes, or `0` if the binary does not contain it.
+fn pe_section_size(pe: &binfarce::pe::Pe<'_>, name: &str) -> Result<u64, Error> {
+    match pe
+        .section_with_name(name)
+        .map_err(|_| Error::InvalidBinary)?
+    {
+        Some(section) => Ok(section.range().map_err(|_| Error::InvalidBinary)?.len() as u64),
+        None => Ok(0),
+    }
+}
+
+/// The load address and size of a single section, used to determine which
+/// `MEMORY` region (see [`crate::memory_regions`][regions]) it was placed in.
+///
+/// [regions]: ../fn.memory_regions.html
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// The section's name, e.g. `.text`.
+    pub name: String,
+    /// The section's load address.
+    pub address: u64,
+    /// The section's size in bytes.
+    pub size: u64,
+}
+
+/// Read the load address and size of all [`PROGRAM_SECTIONS`][program] and
+/// [`DATA_SECTIONS`][data] present in the ELF binary `file`.
+///
+/// Returns an empty `Vec` for non-ELF binaries, since only ELF exposes load
+/// addresses through this tool.
+///
+/// [program]: constant.PROGRAM_SECTIONS.html
+/// [data]: constant.DATA_SECTIONS.html
+pub fn read_sections_from(file: &Path) -> Result<Vec<Section>, Error> {
+    if detect_format(&fs::read(file)?) != Some(Format::Elf) {
+        return Ok(Vec::new());
+    }
+    let file = elf::File::open_path(file)?;
+
+    Ok(PROGRAM_SECTIONS
+        .iter()
+        .chain(DATA_SECTIONS.iter())
+        .filter_map(|name| file.get_section(name))
+        .map(|section| Section {
+            name: section.shdr.name.clone(),
+            address: section.shdr.addr,
+            size: section.shdr.size,
+        })
+        .collect())
+}
+
+/// A single symbol of the `.text` section together with its resolved size
+/// and the crate it was attributed to.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// The demangled name of the symbol.
+    pub name: String,
+    /// The name of the crate the symbol was attributed to, or
+    /// [`UNKNOWN_CRATE`][unknown] if that could not be determined.
+    ///
+    /// [unknown]: constant.UNKNOWN_CRATE.html
+    pub crate_name: String,
+    /// The size of the symbol in bytes.
+    pub size: u64,
+}
+
+/// Read all `.text` symbols from `file` and attribute each of them to the
+/// crate it was compiled from.
+///
+/// The symbol table (`.symtab`) is read and every symbol located in `.text`
+/// is demangled, handling both the legacy (`_ZN...`) and the v0 (`_R...`)
+/// mangling scheme. The first path segment of the demangled name is taken as
+/// the owning crate; symbols that cannot be demangled fall back to
+/// [`UNKNOWN_CRATE`][unknown].
+///
+/// Multiple symbols aliasing the same address are only counted once. Symbols
+/// without an explicit size have their size approximated by the gap to the
+/// next symbol in the same section, falling back to the section's end
+/// address for the last symbol in a section.
+///
+/// # Errors
+/// Only ELF binaries carry the symbol table this function relies on;
+/// [`Error::UnsupportedFormat`][unsupported] is returned for any other
+/// format.
+///
+/// [unknown]: constant.UNKNOWN_CRATE.html
+/// [unsupported]: ../error/enum.Error.html#variant.UnsupportedFormat
+pub fn read_symbols_from(file: &Path) -> Result<Vec<Symbol>, Error> {
+    if detect_format(&fs::read(file)?) != Some(Format::Elf) {
+        return Err(Error::UnsupportedFormat);
+    }
+    let file = elf::File::open_path(file)?;
+    let symtab = file.get_section(".symtab").ok_or(Error::InvalidBinary)?;
+    let mut symbols = file.get_symbols(symtab)?;
+
+    // several symbols can alias the same address (e.g. weak and strong
+    // definitions); keep only one per address so the size is not counted
+    // twice.
+    symbols.sort_by_key(|symbol| (symbol.shndx, symbol.value));
+    symbols.dedup_by_key(|symbol| (symbol.shndx, symbol.value));
+
+    let mut result = Vec::new();
+    for (index, symbol) in symbols.iter().enumerate() {
+        if symbol.name.is_empty() {
+            continue;
+        }
+        let in_text = file
+            .sections
+            .get(symbol.shndx as usize)
+            .map_or(false, |section| section.shdr.name == ".text");
+        if !in_text {
+            continue;
+        }
+
+        let next_symbol_address = symbols[index + 1..]
+            .iter()
+            .filter(|other| other.shndx == symbol.shndx)
+            .map(|other| other.value)
+            .find(|&address| address > symbol.value);
+        let section_end = file
+            .sections
+            .get(symbol.shndx as usize)
+            .map(|section| section.shdr.addr + section.shdr.size);
+        let size = resolve_symbol_size(symbol.value, symbol.size, next_symbol_address, section_end);
+        if size == 0 {
+            continue;
+        }
+
+        let demangled = rustc_demangle::demangle(&symbol.name).to_string();
+        let crate_name = if demangled == symbol.name {
+            UNKNOWN_CRATE.to_owned()
+        } else {
+            owning_crate(&demangled)
+                .filter(|segment| !segment.is_empty())
+                .unwrap_or(UNKNOWN_CRATE)
+                .to_owned()
+        };
+
+        result.push(Symbol {
+            name: demangled,
+            crate_name,
+            size,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Resolve the size of a (possibly zero-sized) ELF symbol table entry.
+///
+/// If `size` is already non-zero, it is used as-is. Otherwise the size is
+/// approximated by the gap to `next_symbol_address`, the next symbol in the
+/// same section; if there is none (this is the last symbol in the section),
+/// `section_end` is used instead, so the final symbol isn't silently dropped.
+/// Returns `0` if neither is available or `value` is already at the end.
+fn resolve_symbol_size(
+    value: u64,
+    size: u64,
+    next_symbol_address: Option<u64>,
+    section_end: Option<u64>,
+) -> u64 {
+    if size != 0 {
+        return size;
+    }
+
+    match next_symbol_address.or(section_end) {
+        Some(end) if end > value => end - value,
+        _ => 0,
+    }
+}
+
+/// Extract the owning crate from a demangled symbol `name`.
+///
+/// For a plain path like `realbin::module::function` this is simply the
+/// first `::`-separated segment. Trait-impl symbols demangle to
+/// `<realbin::Type as core::fmt::Debug>::fmt` instead; a leading `<` is
+/// stripped first so that the segment up to the first of `::`/` as ` is
+/// still the implementing type's crate (`realbin`), not the `Trait` it
+/// implements.
+fn owning_crate(name: &str) -> Option<&str> {
+    let name = name.strip_prefix('<').unwrap_or(name);
+    let end = [name.find("::"), name.find(" as ")]
+        .iter()
+        .filter_map(|position| *position)
+        .min();
+
+    Some(match end {
+        Some(end) => &name[..end],
+        None => name,
+    })
+}
+
+/// Aggregate `symbols` by the crate they were attributed to.
+///
+/// The returned pairs are sorted by size in descending order.
+pub fn crate_sizes(symbols: &[Symbol]) -> Vec<(String, u64)> {
+    let mut sizes: HashMap<&str, u64> = HashMap::new();
+    for symbol in symbols {
+        *sizes.entry(&symbol.crate_name).or_insert(0) += symbol.size;
+    }
+
+    let mut sizes: Vec<_> = sizes
+        .into_iter()
+        .map(|(name, size)| (name.to_owned(), size))
+        .collect();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1));
+
+    sizes
+}
+
+/// Sort `symbols` by size in descending order, for a per-symbol breakdown.
+pub fn sorted_symbols(mut symbols: Vec<Symbol>) -> Vec<Symbol> {
+    symbols.sort_by(|a, b| b.size.cmp(&a.size));
+
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        crate_sizes, detect_format, owning_crate, read_macho_size, read_pe_size,
+        resolve_symbol_size, Format, Symbol,
+    };
+
+    /// Build a minimal synthetic Mach-O 64-bit binary with a single
+    /// `LC_SEGMENT_64` load command containing one section, for exercising
+    /// [`read_macho_size`] without needing a real compiled binary.
+    fn synthetic_macho(segment: &str, section: &str, size: u32) -> Vec<u8> {
+        let mut section_bytes = [0u8; 16];
+        section_bytes[..section.len()].copy_from_slice(section.as_bytes());
+        let mut segment_bytes = [0u8; 16];
+        segment_bytes[..segment.len()].copy_from_slice(segment.as_bytes());
+
+        let mut command = Vec::new();
+        command.extend_from_slice(&0x19u32.to_le_bytes()); // LC_SEGMENT_64
+        command.extend_from_slice(&(8 + 64 + 80u32).to_le_bytes()); // cmdsize
+        command.extend_from_slice(&[0u8; 16]); // segname
+        command.extend_from_slice(&0u64.to_le_bytes()); // vmaddr
+        command.extend_from_slice(&0u64.to_le_bytes()); // vmsize
+        command.extend_from_slice(&0u64.to_le_bytes()); // fileoff
+        command.extend_from_slice(&0u64.to_le_bytes()); // filesize
+        command.extend_from_slice(&0u32.to_le_bytes()); // maxprot
+        command.extend_from_slice(&0u32.to_le_bytes()); // initprot
+        command.extend_from_slice(&1u32.to_le_bytes()); // nsects
+        command.extend_from_slice(&0u32.to_le_bytes()); // flags
+        command.extend_from_slice(&section_bytes); // sectname
+        command.extend_from_slice(&segment_bytes); // segname
+        command.extend_from_slice(&0u64.to_le_bytes()); // addr
+        command.extend_from_slice(&u64::from(size).to_le_bytes()); // size
+        command.extend_from_slice(&0u32.to_le_bytes()); // offset
+        command.extend_from_slice(&[0u8; 28]); // align, reloff, nreloc, flags, padding
+
+        let mut file = Vec::new();
+        file.extend_from_slice(b"\xcf\xfa\xed\xfe"); // magic (64-bit, little endian)
+        file.extend_from_slice(&0u32.to_le_bytes()); // cputype
+        file.extend_from_slice(&0u32.to_le_bytes()); // cpusubtype
+        file.extend_from_slice(&0u32.to_le_bytes()); // filetype
+        file.extend_from_slice(&1u32.to_le_bytes()); // ncmds
+        file.extend_from_slice(&(command.len() as u32).to_le_bytes()); // sizeofcmds
+        file.extend_from_slice(&0u32.to_le_bytes()); // flags
+        file.extend_from_slice(&0u32.to_le_bytes()); // reserved
+        file.extend_from_slice(&command);
+
+        file
+    }
+
+    /// Build a minimal synthetic PE/COFF binary with a single section, for
+    /// exercising [`read_pe_size`] without needing a real compiled binary.
+    fn synthetic_pe(name: &str, size_of_raw_data: u32) -> Vec<u8> {
+        const PE_POINTER: usize = 0x80;
+
+        let mut name_bytes = [0u8; 8];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+
+        let mut file = vec![0u8; PE_POINTER + 4 + 20 + 40];
+        file[0] = b'M';
+        file[1] = b'Z';
+        file[0x3c..0x40].copy_from_slice(&(PE_POINTER as u32).to_le_bytes());
+
+        file[PE_POINTER..PE_POINTER + 4].copy_from_slice(b"PE\0\0");
+        let header = PE_POINTER + 4;
+        file[header..header + 2].copy_from_slice(&0u16.to_le_bytes()); // machine
+        file[header + 2..header + 4].copy_from_slice(&1u16.to_le_bytes()); // number_of_sections
+        file[header + 4..header + 8].copy_from_slice(&0u32.to_le_bytes()); // time_date_stamp
+        file[header + 8..header + 12].copy_from_slice(&0u32.to_le_bytes()); // pointer_to_symbol_table
+        file[header + 12..header + 16].copy_from_slice(&0u32.to_le_bytes()); // number_of_symbols
+        file[header + 16..header + 18].copy_from_slice(&0u16.to_le_bytes()); // size_of_optional_header
+        file[header + 18..header + 20].copy_from_slice(&0u16.to_le_bytes()); // characteristics
+
+        let section = header + 20;
+        file[section..section + 8].copy_from_slice(&name_bytes);
+        file[section + 8..section + 12].copy_from_slice(&0u32.to_le_bytes()); // virtual_size
+        file[section + 12..section + 16].copy_from_slice(&0u32.to_le_bytes()); // virtual_address
+        file[section + 16..section + 20].copy_from_slice(&size_of_raw_data.to_le_bytes());
+        file[section + 20..section + 24].copy_from_slice(&0u32.to_le_bytes()); // pointer_to_raw_data
+
+        file
+    }
+
+    #[test]
+    fn detect_format_recognizes_elf() {
+        assert_eq!(detect_format(b"\x7fELF\0\0\0\0"), Some(Format::Elf));
+    }
+
+    #[test]
+    fn detect_format_recognizes_macho() {
+        assert_eq!(
+            detect_format(b"\xfe\xed\xfa\xcf\0\0\0\0"),
+            Some(Format::MachO)
+        );
+    }
+
+    #[test]
+    fn detect_format_recognizes_pe() {
+        assert_eq!(detect_format(b"MZ\0\0\0\0\0\0"), Some(Format::Pe));
+    }
+
+    #[test]
+    fn detect_format_returns_none_for_unknown_magic() {
+        assert_eq!(detect_format(b"\0\0\0\0\0\0\0\0"), None);
+    }
+
+    #[test]
+    fn read_macho_size_sums_code_and_data_sections() {
+        let code_binary = synthetic_macho("__TEXT", "__text", 42);
+        assert_eq!(read_macho_size(&code_binary).unwrap(), (42, 0));
+
+        let data_binary = synthetic_macho("__DATA", "__data", 13);
+        assert_eq!(read_macho_size(&data_binary).unwrap(), (0, 13));
+    }
+
+    #[test]
+    fn read_macho_size_ignores_unknown_sections() {
+        let binary = synthetic_macho("__TEXT", "__unwind_info", 99);
+        assert_eq!(read_macho_size(&binary).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn read_pe_size_sums_code_and_data_sections() {
+        let code_binary = synthetic_pe(".text", 42);
+        assert_eq!(read_pe_size(&code_binary).unwrap(), (42, 0));
+
+        let data_binary = synthetic_pe(".data", 13);
+        assert_eq!(read_pe_size(&data_binary).unwrap(), (0, 13));
+    }
+
+    #[test]
+    fn read_pe_size_ignores_unknown_sections() {
+        let binary = synthetic_pe(".rsrc", 99);
+        assert_eq!(read_pe_size(&binary).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn resolve_symbol_size_uses_explicit_size_if_present() {
+        assert_eq!(resolve_symbol_size(100, 42, Some(200), Some(300)), 42);
+    }
+
+    #[test]
+    fn resolve_symbol_size_falls_back_to_the_next_symbol_gap() {
+        assert_eq!(resolve_symbol_size(100, 0, Some(150), Some(300)), 50);
+    }
+
+    #[test]
+    fn resolve_symbol_size_falls_back_to_the_section_end_for_the_last_symbol() {
+        assert_eq!(resolve_symbol_size(100, 0, None, Some(300)), 200);
+    }
+
+    #[test]
+    fn resolve_symbol_size_is_zero_if_nothing_follows() {
+        assert_eq!(resolve_symbol_size(100, 0, None, None), 0);
+    }
+
+    #[test]
+    fn owning_crate_of_a_plain_path() {
+        assert_eq!(owning_crate("realbin::module::function"), Some("realbin"));
+    }
+
+    #[test]
+    fn owning_crate_of_a_trait_impl() {
+        assert_eq!(
+            owning_crate("<realbin::Foo as core::fmt::Debug>::fmt"),
+            Some("realbin")
+        );
+    }
+
+    #[test]
+    fn crate_sizes_aggregates_by_crate_name() {
+        let symbols = vec![
+            Symbol {
+                name: "a".to_owned(),
+                crate_name: "realbin".to_owned(),
+                size: 10,
+            },
+            Symbol {
+                name: "b".to_owned(),
+                crate_name: "realbin".to_owned(),
+                size: 5,
+            },
+            Symbol {
+                name: "c".to_owned(),
+                crate_name: "core".to_owned(),
+                size: 20,
+            },
+        ];
+
+        assert_eq!(
+            crate_sizes(&symbols),
+            vec![("core".to_owned(), 20), ("realbin".to_owned(), 15)]
+        );
+    }
+}
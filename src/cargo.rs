@@ -34,9 +34,49 @@ fn contains_manifest(directory: &Path) -> bool {
         .unwrap_or(false)
 }
 
+/// Returns the package name declared by the crate root's `Cargo.toml`.
+///
+/// # Errors
+/// This function returns [`NotACrate`][not_a_crate] if no `Cargo.toml` could
+/// be found (see [`root`][root]), or [`InvalidManifest`][invalid] if it was
+/// found but does not declare a `name` under `[package]`.
+///
+/// [not_a_crate]: ../error/enum.Error.html#variant.NotACrate
+/// [root]: fn.root.html
+/// [invalid]: ../error/enum.Error.html#variant.InvalidManifest
+pub fn crate_name() -> Result<String, Error> {
+    let manifest = fs::read_to_string(root()?.join("Cargo.toml"))?;
+    package_name(&manifest).ok_or(Error::InvalidManifest)
+}
+
+/// Extract the `name` field of the `[package]` section out of a `Cargo.toml`'s
+/// `content`.
+///
+/// This only handles the common `name = "..."` form; it is not a general
+/// purpose TOML parser.
+fn package_name(content: &str) -> Option<String> {
+    let package_start = content.find("[package]")?;
+    let section = &content[package_start..];
+    let section_end = section[1..]
+        .find("\n[")
+        .map_or(section.len(), |end| end + 1);
+    let section = &section[..section_end];
+
+    let line = section.lines().find(|line| {
+        line.trim_start()
+            .strip_prefix("name")
+            .map_or(false, |rest| rest.trim_start().starts_with('='))
+    })?;
+    let value = line[line.find('=')? + 1..].trim();
+    let value = value.strip_prefix('"')?;
+    let end = value.find('"')?;
+
+    Some(value[..end].to_owned())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{contains_manifest, env, root, Error};
+    use super::{contains_manifest, env, package_name, root, Error};
 
     #[test]
     fn crate_root_contains_manifest() {
@@ -57,4 +97,21 @@ mod tests {
         assert_eq!(root().unwrap_err(), Error::NotACrate);
     }
 
+    #[test]
+    fn package_name_extracts_the_name_field() {
+        let manifest = "[package]\nname = \"cargo-size\"\nversion = \"1.0.0\"\n";
+        assert_eq!(package_name(manifest), Some("cargo-size".to_owned()));
+    }
+
+    #[test]
+    fn package_name_ignores_fields_from_other_sections() {
+        let manifest =
+            "[package]\nname = \"cargo-size\"\n\n[dependencies]\nname = \"not-this-one\"\n";
+        assert_eq!(package_name(manifest), Some("cargo-size".to_owned()));
+    }
+
+    #[test]
+    fn package_name_is_none_without_a_package_section() {
+        assert_eq!(package_name("[dependencies]\nfoo = \"1\"\n"), None);
+    }
 }
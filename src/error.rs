@@ -16,6 +16,9 @@ pub enum Error {
     BinaryNotFound,
     /// The binary was invalid
     InvalidBinary,
+    /// The binary's format (e.g. ELF, Mach-O, PE) could not be determined, or
+    /// is not supported.
+    UnsupportedFormat,
     /// An I/O error
     IoError(io::Error),
 }
@@ -31,6 +34,9 @@ impl Display for Error {
             Error::InvalidBinary => {
                 write!(f, "The binary has an invalid format")
             }
+            Error::UnsupportedFormat => {
+                write!(f, "The binary's format is not supported")
+            }
             Error::IoError(e) => write!(f, "I/O error ({})", e),
         }
     }
@@ -53,6 +59,7 @@ impl PartialEq for Error {
             (Error::BuildError, Error::BuildError) => true,
             (Error::BinaryNotFound, Error::BinaryNotFound) => true,
             (Error::InvalidBinary, Error::InvalidBinary) => true,
+            (Error::UnsupportedFormat, Error::UnsupportedFormat) => true,
             (Error::IoError(_), Error::IoError(_)) => true,
             _ => false,
         }
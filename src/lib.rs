@@ -37,6 +37,17 @@
 //! two sections `flash` and `ram` (case is ignored). The size of those two
 //! memories is used to calculate the percentages.
 //!
+//! If the built binary carries load addresses (which is the case for most
+//! embedded ELF binaries) and at least one section's address falls into a
+//! region declared in `memory.x` (any name, e.g. `FLASH`, `RAM`, `CCMRAM` or
+//! a second RAM bank), each region's sections are matched up by address and
+//! one fill line is printed per declared region instead of the combined
+//! `Program`/`Data` percentages.
+//!
+//! Pass `--human` to print sizes with binary unit suffixes (e.g. `54.3 KiB`)
+//! instead of raw byte counts, and `--baseline <file>` to compare the result
+//! against (and then update) a previously recorded size baseline.
+//!
 //! If the binary (either the development or the release one, as specified) is
 //! not up-to-date, cargo is used to build it.
 //!
@@ -55,17 +66,27 @@
 //!   platform. Then the binary is located in a subdirectory named as the
 //!   target. The application tries some known platform, but if yours is not
 //!   known yet, the command will fail.
-//! - The binary has an invalid format, e.g. it is not an ELF-file or it is
-//!   corrupt.
+//! - The binary has an unsupported or invalid format, e.g. it is neither an
+//!   ELF, Mach-O nor PE/COFF file, or it is corrupt.
+extern crate binfarce;
+extern crate bytesize;
 extern crate colored;
 extern crate elf;
 extern crate ldscript_parser;
+extern crate rustc_demangle;
 
 use crate::error::Error;
+use colored::Colorize;
 use ldscript_parser::RootItem::Memory;
 use std::env;
 use std::fs;
+use std::path::Path;
+
+/// The number of crates/symbols printed by the `--crates`/`--symbols`
+/// breakdown.
+pub const BREAKDOWN_ENTRIES: usize = 20;
 
+pub mod baseline;
 pub mod binary;
 pub mod cargo;
 pub mod error;
@@ -121,3 +142,363 @@ pub fn memory_size() -> Option<(u64, u64)> {
             }
         })
 }
+
+/// A single `MEMORY` region as declared in `memory.x`.
+#[derive(Debug, Clone)]
+pub struct Region {
+    /// The region's name, e.g. `FLASH` or `CCMRAM`.
+    pub name: String,
+    /// The region's start address.
+    pub origin: u64,
+    /// The region's size in bytes.
+    pub length: u64,
+}
+
+/// Parse every `MEMORY` region declared in `memory.x`, if present.
+///
+/// Returns an empty `Vec` if the file does not exist or has an invalid
+/// format.
+pub fn memory_regions() -> Vec<Region> {
+    fs::read_to_string("memory.x")
+        .ok()
+        .and_then(|content| ldscript_parser::parse(&content).ok())
+        .map(|items| {
+            items
+                .into_iter()
+                .filter_map(|item| match item {
+                    Memory { regions } => Some(regions),
+                    _ => None,
+                })
+                .flatten()
+                .map(|region| Region {
+                    name: region.name,
+                    origin: region.origin,
+                    length: region.length,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Compute the number of bytes used in each of `regions`, by checking which
+/// region each of `sections`' load address falls into.
+///
+/// A section is attributed to a region if its address lies within
+/// `[region.origin, region.origin + region.length)`. Returns `None` if none
+/// of `sections` could be attributed to any region (e.g. because the binary
+/// carries no address information), so callers can fall back to a simpler
+/// summary.
+pub fn region_usage(
+    regions: &[Region],
+    sections: &[binary::Section],
+) -> Option<Vec<(String, u64, u64)>> {
+    let mut used = vec![0; regions.len()];
+    let mut matched_any = false;
+
+    for section in sections {
+        if let Some(index) = regions.iter().position(|region| {
+            section.address >= region.origin && section.address < region.origin + region.length
+        }) {
+            used[index] += section.size;
+            matched_any = true;
+        }
+    }
+
+    if !matched_any {
+        return None;
+    }
+
+    Some(
+        regions
+            .iter()
+            .zip(used)
+            .map(|(region, used)| (region.name.clone(), used, region.length))
+            .collect(),
+    )
+}
+
+/// Read the path given to `--baseline <file>`, if present.
+pub fn baseline_path() -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+    args.windows(2)
+        .find(|pair| pair[0] == "--baseline")
+        .map(|pair| pair[1].clone())
+}
+
+/// Read the previously recorded baseline, if `--baseline <file>` was given,
+/// then overwrite it with the current result.
+///
+/// Returns `None` if `--baseline` was not given, or if the file did not yet
+/// exist (it is still written so the next run has something to compare
+/// against).
+pub fn update_baseline(
+    code: u64,
+    data: u64,
+    usage: Option<&[(String, u64, u64)]>,
+) -> Result<Option<baseline::Baseline>, Error> {
+    let path = match baseline_path() {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let path = Path::new(&path);
+
+    let previous = baseline::read(path);
+    baseline::write(path, code, data, usage.unwrap_or(&[]))?;
+
+    Ok(previous)
+}
+
+/// Format `value` either as a plain byte count or, if `human` is set, using
+/// binary unit suffixes (e.g. `54.3 KiB`) via the `bytesize` crate.
+pub fn format_size(value: u64, human: bool) -> String {
+    if human {
+        format!("{:>10}", bytesize::to_string(value, true))
+    } else {
+        format!("{:>7} bytes", value)
+    }
+}
+
+/// Format the signed difference between `value` and `previous` the same way
+/// as [`format_size`][size], but colored green for a shrink and red for a
+/// growth.
+///
+/// [size]: fn.format_size.html
+pub fn format_diff(value: u64, previous: u64, human: bool) -> colored::ColoredString {
+    let diff = value as i64 - previous as i64;
+    let text = if human {
+        let sign = if diff < 0 { "-" } else { "+" };
+        format!("{}{}", sign, bytesize::to_string(diff.abs() as u64, true))
+    } else {
+        format!("{:+} bytes", diff)
+    };
+
+    if diff > 0 {
+        text.red()
+    } else if diff < 0 {
+        text.green()
+    } else {
+        text.normal()
+    }
+}
+
+/// Format a comparison against a previously recorded `--baseline`, including
+/// one line per region present in both `previous` and the current `usage`.
+pub fn format_baseline_diff(
+    previous: &baseline::Baseline,
+    code: u64,
+    data: u64,
+    usage: Option<&[(String, u64, u64)]>,
+    human: bool,
+) -> String {
+    let mut diff = format!(
+        "\n\n     Baseline Diff
+     -------------
+     Program: {}
+     Data:    {}",
+        format_diff(code, previous.program_bytes, human),
+        format_diff(data, previous.data_bytes, human),
+    );
+
+    for (name, used, _) in usage.unwrap_or(&[]) {
+        if let Some((_, previous_used)) = previous.regions.iter().find(|(n, _)| n == name) {
+            diff += &format!(
+                "\n     {:<9}{}",
+                format!("{}:", name.to_uppercase()),
+                format_diff(*used, *previous_used, human)
+            );
+        }
+    }
+
+    diff
+}
+
+/// Format a `--crates`/`--symbols` breakdown as a table of the first
+/// [`BREAKDOWN_ENTRIES`][entries] of `entries`, in the order given (the
+/// entries are not sorted here).
+///
+/// `total` is the sum of all (not just the printed) entries' sizes and is
+/// used as the denominator of the printed percentage, i.e. the share of
+/// `.text`.
+///
+/// [entries]: constant.BREAKDOWN_ENTRIES.html
+pub fn format_breakdown(entries: &[(String, u64)], total: u64, human: bool) -> String {
+    let mut table = String::from("\n\n     Name                                          Bytes        %\n     --------------------------------------------------------\n");
+    for (name, size) in entries.iter().take(BREAKDOWN_ENTRIES) {
+        let percentage = *size as f32 / total as f32 * 100.0;
+        table += &format!(
+            "     {:<40} {} {:>6.1}%\n",
+            name,
+            format_size(*size, human),
+            percentage
+        );
+    }
+
+    table
+}
+
+/// Format the result as a single-line-indented JSON object, suitable for CI
+/// pipelines to diff across commits.
+///
+/// `usage`, if present, is added as the `"regions"` array and takes priority
+/// over `memory` for the `program_capacity`/`data_capacity` fields, which are
+/// kept for the common two-region (flash/ram) case. `crates`, if present, is
+/// added as the `"crates"` array. `baseline`, if present, is added as a
+/// `"baseline"` object holding the same diff [`format_baseline_diff`][diff]
+/// prints for the text output.
+///
+/// [diff]: fn.format_baseline_diff.html
+pub fn format_json(
+    code: u64,
+    data: u64,
+    memory: Option<(u64, u64)>,
+    usage: Option<&[(String, u64, u64)]>,
+    crates: Option<&[(String, u64)]>,
+    baseline: Option<&baseline::Baseline>,
+) -> String {
+    let mut json = format!(
+        "{{\n  \"program_bytes\": {},\n  \"data_bytes\": {}",
+        code, data
+    );
+
+    if let Some(usage) = usage {
+        let entries: Vec<String> = usage
+            .iter()
+            .map(|(name, used, length)| {
+                let percentage = *used as f32 / *length as f32 * 100.0;
+                format!(
+                    "{{ \"name\": {:?}, \"used_bytes\": {}, \"capacity_bytes\": {}, \"fill_percent\": {:.1} }}",
+                    name, used, length, percentage
+                )
+            })
+            .collect();
+        json += &format!(",\n  \"regions\": [{}]", entries.join(", "));
+    } else if let Some((code_memory, data_memory)) = memory {
+        let code_percentage = code as f32 / code_memory as f32 * 100.0;
+        let data_percentage = data as f32 / data_memory as f32 * 100.0;
+        json += &format!(
+            ",\n  \"program_capacity\": {},\n  \"data_capacity\": {},\n  \"program_fill_percent\": {:.1},\n  \"data_fill_percent\": {:.1}",
+            code_memory, data_memory, code_percentage, data_percentage
+        );
+    }
+
+    if let Some(crates) = crates {
+        let entries: Vec<String> = crates
+            .iter()
+            .map(|(name, size)| format!("{{ \"name\": {:?}, \"bytes\": {} }}", name, size))
+            .collect();
+        json += &format!(",\n  \"crates\": [{}]", entries.join(", "));
+    }
+
+    if let Some(previous) = baseline {
+        json += &format!(
+            ",\n  \"baseline\": {}",
+            format_baseline_diff_json(previous, code, data, usage)
+        );
+    }
+
+    json += "\n}";
+
+    json
+}
+
+/// Format the `"baseline"` object embedded in [`format_json`][json]'s output.
+///
+/// [json]: fn.format_json.html
+fn format_baseline_diff_json(
+    previous: &baseline::Baseline,
+    code: u64,
+    data: u64,
+    usage: Option<&[(String, u64, u64)]>,
+) -> String {
+    let mut diff = format!(
+        "{{ \"program_bytes_diff\": {}, \"data_bytes_diff\": {}",
+        code as i64 - previous.program_bytes as i64,
+        data as i64 - previous.data_bytes as i64,
+    );
+
+    let entries: Vec<String> = usage
+        .unwrap_or(&[])
+        .iter()
+        .filter_map(|(name, used, _)| {
+            previous
+                .regions
+                .iter()
+                .find(|(previous_name, _)| previous_name == name)
+                .map(|(_, previous_used)| {
+                    format!(
+                        "{{ \"name\": {:?}, \"used_bytes_diff\": {} }}",
+                        name,
+                        *used as i64 - *previous_used as i64
+                    )
+                })
+        })
+        .collect();
+    if !entries.is_empty() {
+        diff += &format!(", \"regions_diff\": [{}]", entries.join(", "));
+    }
+
+    diff += " }";
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{binary::Section, region_usage, Region};
+
+    fn region(name: &str, origin: u64, length: u64) -> Region {
+        Region {
+            name: name.to_owned(),
+            origin,
+            length,
+        }
+    }
+
+    fn section(name: &str, address: u64, size: u64) -> Section {
+        Section {
+            name: name.to_owned(),
+            address,
+            size,
+        }
+    }
+
+    #[test]
+    fn sections_are_attributed_to_the_region_their_address_falls_into() {
+        let regions = vec![
+            region("FLASH", 0x0800_0000, 0x10000),
+            region("RAM", 0x2000_0000, 0x2000),
+        ];
+        let sections = vec![
+            section(".text", 0x0800_0000, 0x1234),
+            section(".data", 0x2000_0000, 0x10),
+        ];
+
+        let usage = region_usage(&regions, &sections).unwrap();
+        assert_eq!(
+            usage,
+            vec![
+                ("FLASH".to_owned(), 0x1234, 0x10000),
+                ("RAM".to_owned(), 0x10, 0x2000)
+            ]
+        );
+    }
+
+    #[test]
+    fn sections_outside_any_region_are_ignored() {
+        let regions = vec![region("FLASH", 0x0800_0000, 0x10000)];
+        let sections = vec![
+            section(".text", 0x0800_0000, 0x100),
+            section(".far_away", 0x9000_0000, 0x100),
+        ];
+
+        let usage = region_usage(&regions, &sections).unwrap();
+        assert_eq!(usage, vec![("FLASH".to_owned(), 0x100, 0x10000)]);
+    }
+
+    #[test]
+    fn no_matching_section_returns_none() {
+        let regions = vec![region("FLASH", 0x0800_0000, 0x10000)];
+        let sections = vec![section(".text", 0x9000_0000, 0x100)];
+
+        assert!(region_usage(&regions, &sections).is_none());
+    }
+}
@@ -20,99 +20,123 @@
 //! binary if necessary and prints its size.
 //!
 //! If the file `memory.x` is not found the percentages are omitted.
-extern crate colored;
-extern crate elf;
-extern crate ldscript_parser;
+//!
+//! If the built binary carries load addresses and at least one section falls
+//! into a region declared in `memory.x`, one fill line is printed per
+//! declared region instead.
+//!
+//! Pass `--human` to print sizes with binary unit suffixes (e.g. `54.3 KiB`)
+//! instead of raw byte counts, and `--baseline <file>` to compare the result
+//! against (and then update) a previously recorded size baseline.
+extern crate cargo_size;
 
-use crate::error::Error;
-use crate::mode::Mode;
+use cargo_size::binary;
+use cargo_size::change_directory;
+use cargo_size::error::Error;
+use cargo_size::mode::{Mode, OutputFormat};
+use cargo_size::{
+    format_baseline_diff, format_breakdown, format_json, format_size, memory_regions, memory_size,
+    region_usage, update_baseline,
+};
 use colored::Colorize;
-use ldscript_parser::RootItem::Memory;
 use std::env;
-use std::fs;
 use std::process;
 
-mod binary;
-mod cargo;
-mod error;
-mod mode;
-
 /// Try to execute the whole program or return at the first error.
 ///
 /// On success, the function returns the program output.
 fn try_main() -> Result<String, Error> {
     let mode = Mode::new();
+    let human = env::args().any(|arg| arg == "--human");
 
     change_directory()?;
-    mode.build_binary()?;
-    let binary = mode.binary()?;
+    let binary = mode.build_binary()?;
     let (code, data) = binary::read_size_from(&binary)?;
 
-    if let Some((code_memory, data_memory)) = memory_size() {
+    let crates = if env::args().any(|arg| arg == "--crates") {
+        let symbols = binary::read_symbols_from(&binary)?;
+        Some(binary::crate_sizes(&symbols))
+    } else {
+        None
+    };
+
+    let sections = binary::read_sections_from(&binary)?;
+    let regions = memory_regions();
+    let usage = region_usage(&regions, &sections);
+
+    let previous_baseline = update_baseline(code, data, usage.as_deref())?;
+
+    if let OutputFormat::Json = OutputFormat::new() {
+        return Ok(format_json(
+            code,
+            data,
+            memory_size(),
+            usage.as_deref(),
+            crates.as_deref(),
+            previous_baseline.as_ref(),
+        ));
+    }
+
+    let mut output = if let Some(usage) = &usage {
+        let mut text = format!(
+            "Memory Usage
+             ------------
+             Program: {}
+             Data:    {}",
+            format_size(code, human),
+            format_size(data, human)
+        );
+        for (name, used, length) in usage {
+            let percentage = *used as f32 / *length as f32 * 100.0;
+            text += &format!(
+                "\n             {:<9}{} ({:.1}% full)",
+                format!("{}:", name.to_uppercase()),
+                format_size(*used, human),
+                percentage
+            );
+        }
+        text
+    } else if let Some((code_memory, data_memory)) = memory_size() {
         let code_percentage = code as f32 / code_memory as f32 * 100.0;
         let data_percentage = data as f32 / data_memory as f32 * 100.0;
-        Ok(format!(
+        format!(
             "Memory Usage
              ------------
-             Program: {:>7} bytes ({:.1}% full)
-             Data:    {:>7} bytes ({:.1}% full)",
-            code, code_percentage, data, data_percentage
-        ))
+             Program: {} ({:.1}% full)
+             Data:    {} ({:.1}% full)",
+            format_size(code, human),
+            code_percentage,
+            format_size(data, human),
+            data_percentage
+        )
     } else {
-        Ok(format!(
+        format!(
             "Memory Usage
              ------------
-             Program: {:>7} bytes
-             Data:    {:>7} bytes",
-            code, data
-        ))
-    }
-}
-
-/// Changes the current working directory to the crate root if possible.
-fn change_directory() -> Result<(), Error> {
-    env::set_current_dir(cargo::root()?)?;
+             Program: {}
+             Data:    {}",
+            format_size(code, human),
+            format_size(data, human)
+        )
+    };
 
-    Ok(())
-}
+    if let Some(crates) = &crates {
+        output += &format_breakdown(crates, crates.iter().map(|(_, size)| size).sum(), human);
+    } else if env::args().any(|arg| arg == "--symbols") {
+        let symbols = binary::sorted_symbols(binary::read_symbols_from(&binary)?);
+        let total = symbols.iter().map(|symbol| symbol.size).sum();
+        let entries: Vec<_> = symbols
+            .into_iter()
+            .map(|symbol| (symbol.name, symbol.size))
+            .collect();
+        output += &format_breakdown(&entries, total, human);
+    }
 
-/// Read the file `memory.x` if present and return the program and data memory
-/// size.
-///
-/// If the file does not exist or has an invalid format, `None` is returned. To
-/// be valid, there have to be two sections present in the memory section, which
-/// are named `flash` and `ram` (case is ignored).
-fn memory_size() -> Option<(u64, u64)> {
-    fs::read_to_string("memory.x")
-        .ok()
-        .and_then(|content| ldscript_parser::parse(&content).ok())
-        .and_then(|items| {
-            for item in items {
-                match item {
-                    Memory { regions } => return Some(regions),
-                    _ => {}
-                }
-            }
-            None
-        })
-        .and_then(|sections| {
-            let mut code = 0;
-            let mut data = 0;
-            for section in sections {
-                if section.name.to_lowercase() == "flash" {
-                    code += section.length;
-                }
-                if section.name.to_lowercase() == "ram" {
-                    data += section.length;
-                }
-            }
+    if let Some(previous) = &previous_baseline {
+        output += &format_baseline_diff(previous, code, data, usage.as_deref(), human);
+    }
 
-            if code != 0 && data != 0 {
-                Some((code, data))
-            } else {
-                None
-            }
-        })
+    Ok(output)
 }
 
 /// The program entry point.
@@ -3,7 +3,7 @@ use crate::cargo;
 use crate::error::Error;
 use std::env;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 /// The supported cross platform targets.
 ///
@@ -16,6 +16,36 @@ pub const SUPPORTED_CROSS_PLATFORMS: [&str; 4] = [
     "thumbv7em-none-eabihf",
 ];
 
+/// The output format requested on the command line.
+pub enum OutputFormat {
+    /// The default, human readable text block.
+    Text,
+    /// Machine readable JSON, for CI pipelines tracking size regressions.
+    Json,
+}
+impl OutputFormat {
+    /// Determine the requested output format from the command line
+    /// arguments.
+    ///
+    /// Recognizes `--message-format=json` and `--output json`; any other (or
+    /// missing) argument results in [`OutputFormat::Text`][text].
+    ///
+    /// [text]: enum.OutputFormat.html#variant.Text
+    pub fn new() -> OutputFormat {
+        let args: Vec<String> = env::args().collect();
+        let wants_json = args.iter().any(|arg| arg == "--message-format=json")
+            || args
+                .windows(2)
+                .any(|pair| pair[0] == "--output" && pair[1] == "json");
+
+        if wants_json {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Text
+        }
+    }
+}
+
 /// The mode of the tool (debug or release).
 pub enum Mode {
     Debug,
@@ -32,22 +62,62 @@ impl Mode {
         }
     }
 
-    /// Build the binary of the crate.
-    pub fn build_binary(&self) -> Result<(), Error> {
-        let status = match self {
-            Mode::Debug => Command::new("cargo").arg("build").status()?,
-            Mode::Release => Command::new("cargo")
-                .args(&["build", "--release"])
-                .status()?,
-        };
-        if !status.success() {
-            Err(Error::BuildError)
-        } else {
-            Ok(())
+    /// Build the binary of the crate and return the path to it.
+    ///
+    /// Cargo is run with `--message-format=json-render-diagnostics`, which
+    /// still renders human-readable build progress/diagnostics to `stderr`,
+    /// but additionally emits one JSON message per line on `stdout`. Those
+    /// lines are scanned for the `compiler-artifact` message of the crate's
+    /// own `bin` target (matched by name against [`cargo::crate_name`][name]),
+    /// whose `executable` field is the authoritative path to the binary that
+    /// was just built. This works regardless of the target triple, a custom
+    /// `target-dir`, or a workspace layout, and picks the right binary even
+    /// if the package (like this one) has more than one `[[bin]]` target.
+    ///
+    /// If the crate name could not be determined, the last `bin` artifact
+    /// message is used, whatever its name. If no such message is found (e.g.
+    /// with a cargo version that does not emit one), the old
+    /// target-directory heuristic in [`binary`][binary] is used as a
+    /// fallback.
+    ///
+    /// [name]: ../cargo/fn.crate_name.html
+    /// [binary]: #method.binary
+    pub fn build_binary(&self) -> Result<PathBuf, Error> {
+        let mut args = vec!["build", "--message-format=json-render-diagnostics"];
+        if let Mode::Release = self {
+            args.push("--release");
+        }
+
+        let output = Command::new("cargo")
+            .args(&args)
+            .stderr(Stdio::inherit())
+            .output()?;
+        if !output.status.success() {
+            return Err(Error::BuildError);
+        }
+
+        let name = cargo::crate_name().ok();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let artifact = stdout
+            .lines()
+            .filter(|line| {
+                line.contains("\"reason\":\"compiler-artifact\"") && target_kind_is_bin(line)
+            })
+            .filter(|line| match &name {
+                Some(name) => json_string_field(line, "name").as_deref() == Some(name.as_str()),
+                None => true,
+            })
+            .filter_map(|line| json_string_field(line, "executable"))
+            .map(PathBuf::from)
+            .last();
+
+        match artifact {
+            Some(path) => Ok(path),
+            None => self.binary(),
         }
     }
 
-    /// Query the path to the binary binary.
+    /// Query the path to the binary by searching the target directory.
     ///
     /// Dependent of the mode the method searches for the binary in the `debug`
     /// or `release` folder. If the binary is not in that folder, some other
@@ -55,6 +125,11 @@ impl Mode {
     /// only ARM targets are supported.
     ///
     /// If the binary can not be found a `BinaryNotFound` error is returned.
+    ///
+    /// This is only used as a fallback by [`build_binary`][build] if cargo's
+    /// build artifact stream did not yield a usable path.
+    ///
+    /// [build]: #method.build_binary
     pub fn binary(&self) -> Result<PathBuf, Error> {
         let target_dir = env::current_dir()?.join("target");
         let name = cargo::crate_name()?;
@@ -89,3 +164,70 @@ impl Mode {
             .ok_or(Error::BinaryNotFound)
     }
 }
+
+/// Query, if a `cargo build --message-format=json` message line's
+/// `target.kind` array contains `"bin"`.
+fn target_kind_is_bin(line: &str) -> bool {
+    line.find("\"kind\":[")
+        .and_then(|start| {
+            let rest = &line[start..];
+            rest.find(']').map(|end| rest[..end].contains("\"bin\""))
+        })
+        .unwrap_or(false)
+}
+
+/// Extract the string value of the first JSON field named `key` out of `line`,
+/// at any nesting level (e.g. `name` inside `target`).
+///
+/// This only handles the compact, single-line JSON objects cargo emits for
+/// `--message-format=json`; it is not a general purpose JSON parser.
+/// Returns `None` if `key` is absent or its value is `null`.
+fn json_string_field(line: &str, key: &str) -> Option<String> {
+    let needle = format!("\"{}\":", key);
+    let value = &line[line.find(&needle)? + needle.len()..];
+
+    if value.starts_with("null") {
+        return None;
+    }
+    let value = value.strip_prefix('"')?;
+    let end = value.find('"')?;
+
+    Some(value[..end].to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_string_field, target_kind_is_bin};
+
+    #[test]
+    fn bin_target_is_recognized() {
+        let line = r#"{"reason":"compiler-artifact","target":{"kind":["bin"],"name":"cargo-size"},"executable":"/tmp/cargo-size"}"#;
+        assert!(target_kind_is_bin(line));
+    }
+
+    #[test]
+    fn lib_target_is_not_a_bin_target() {
+        let line = r#"{"reason":"compiler-artifact","target":{"kind":["lib"],"name":"cargo_size"},"executable":null}"#;
+        assert!(!target_kind_is_bin(line));
+    }
+
+    #[test]
+    fn json_string_field_extracts_value() {
+        let line = r#"{"reason":"compiler-artifact","target":{"kind":["bin"],"name":"cargo-size"},"executable":"/tmp/cargo-size"}"#;
+        assert_eq!(
+            json_string_field(line, "name"),
+            Some("cargo-size".to_owned())
+        );
+        assert_eq!(
+            json_string_field(line, "executable"),
+            Some("/tmp/cargo-size".to_owned())
+        );
+    }
+
+    #[test]
+    fn json_string_field_is_none_for_null_or_missing() {
+        let line = r#"{"reason":"compiler-artifact","executable":null}"#;
+        assert_eq!(json_string_field(line, "executable"), None);
+        assert_eq!(json_string_field(line, "missing"), None);
+    }
+}